@@ -20,17 +20,450 @@ extern crate tokio;
 extern crate veloren_client;
 extern crate veloren_common;
 
-use std::{env, sync::Arc, time::Duration};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
+use serde::Deserialize;
 use tokio::runtime::Runtime;
 use veloren_client::{addr::ConnectionArgs, Client, Event, MarkerAllocator, WorldExt};
 use veloren_common::{
     clock::Clock,
     comp::{self, ChatType},
-    trade::{ReducedInventory, TradeAction},
+    trade::{PendingTrade, TradeAction},
     uid::{Uid, UidAllocator},
+    vek::{Vec2, Vec3},
 };
 
+/// Item definition id of the in-game currency.
+const COINS: &str = "common.items.utility.coins";
+
+/// How far the bot may drift from its anchor before it walks back.
+const POSITION_THRESHOLD: f32 = 1.0;
+
+/// A cardinal facing, stored in the config and converted to a yaw for the
+/// character's look direction.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    North,
+    East,
+    South,
+    West,
+}
+
+impl Direction {
+    /// Yaw in radians about the Z axis, with north at zero.
+    fn yaw(self) -> f32 {
+        use std::f32::consts::{FRAC_PI_2, PI};
+        match self {
+            Direction::North => 0.0,
+            Direction::East => -FRAC_PI_2,
+            Direction::South => PI,
+            Direction::West => FRAC_PI_2,
+        }
+    }
+
+    /// The horizontal look direction corresponding to this facing.
+    fn look_dir(self) -> comp::Dir {
+        let yaw = self.yaw();
+        comp::Dir::from_unnormalized(Vec3::new(-yaw.sin(), yaw.cos(), 0.0)).unwrap_or_default()
+    }
+}
+
+/// How the bot prices a trade. `Take` just stores whatever is handed over (the
+/// bank behaviour); `Buy`/`Sell` turn it into a shop priced in coins.
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TradeMode {
+    #[default]
+    Take,
+    Buy,
+    Sell,
+}
+
+/// Humanoid appearance used when the bot has to create its own character.
+#[derive(Clone, Debug, Deserialize)]
+pub struct BodyConfig {
+    pub species: comp::body::humanoid::Species,
+    pub body_type: comp::body::humanoid::BodyType,
+    pub hair_style: u8,
+    pub beard: u8,
+    pub eyes: u8,
+    pub accessory: u8,
+    pub hair_color: u8,
+    pub skin: u8,
+    pub eye_color: u8,
+}
+
+impl From<&BodyConfig> for comp::body::humanoid::Body {
+    fn from(cfg: &BodyConfig) -> Self {
+        comp::body::humanoid::Body {
+            species: cfg.species,
+            body_type: cfg.body_type,
+            hair_style: cfg.hair_style,
+            beard: cfg.beard,
+            eyes: cfg.eyes,
+            accessory: cfg.accessory,
+            hair_color: cfg.hair_color,
+            skin: cfg.skin,
+            eye_color: cfg.eye_color,
+        }
+    }
+}
+
+/// Runtime configuration loaded from `config.toml`, so the bot can be
+/// deployed without recompiling.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Config {
+    pub username: String,
+    pub password: String,
+    pub server_addr: String,
+    /// Alias of the character to request when logging in.
+    pub character_name: String,
+    /// Aliases allowed to invite, trade with, and command the bot.
+    pub authorized_users: Vec<String>,
+    pub body: BodyConfig,
+    /// Whether the bot stores items, buys them, or sells them.
+    #[serde(default)]
+    pub mode: TradeMode,
+    /// Item definition id -> coin price the bot pays when buying.
+    #[serde(default)]
+    pub buy_prices: HashMap<String, u32>,
+    /// Item definition id -> coin price the bot charges when selling.
+    #[serde(default)]
+    pub sell_prices: HashMap<String, u32>,
+    /// World position the bot anchors itself to, if set.
+    #[serde(default)]
+    pub position: Option<[f32; 3]>,
+    /// Direction the bot faces once anchored, if set.
+    #[serde(default)]
+    pub orientation: Option<Direction>,
+    /// Aliases allowed to run admin commands such as `announce`.
+    #[serde(default)]
+    pub admins: Vec<String>,
+    /// Message periodically broadcast to nearby players, if set.
+    #[serde(default)]
+    pub announcement: Option<String>,
+    /// Seconds between automatic announcements.
+    #[serde(default = "default_announcement_interval")]
+    pub announcement_interval: u64,
+}
+
+/// Default spacing between automatic announcements, in seconds.
+fn default_announcement_interval() -> u64 {
+    300
+}
+
+impl Config {
+    /// Reads and parses `config.toml` from the current working directory.
+    fn load() -> Config {
+        let contents = fs::read_to_string("config.toml").expect("Unable to read config.toml");
+        toml::from_str(&contents).expect("Unable to parse config.toml")
+    }
+
+    /// Returns whether the given alias is on the authorized whitelist.
+    fn is_authorized(&self, alias: &str) -> bool {
+        self.authorized_users.iter().any(|a| a == alias)
+    }
+
+    /// Returns whether the given alias may run admin commands.
+    fn is_admin(&self, alias: &str) -> bool {
+        self.admins.iter().any(|a| a == alias)
+    }
+}
+
+/// Per-user ledger mapping an item's persistence id to the quantity the bot is
+/// holding on that player's behalf.
+type Ledger = HashMap<String, u32>;
+
+/// Book-keeping for a trade that is currently open. The bot snapshots its own
+/// inventory when the trade begins so the ledger can be reconciled against the
+/// physical inventory once the trade resolves.
+struct TradeSession {
+    alias: String,
+    before: Ledger,
+}
+
+/// The storage backend. Deposits and withdrawals are committed by diffing the
+/// bot's physical inventory across a trade, so the per-user ledgers always add
+/// up to what the bot is actually holding — a mid-trade cancellation leaves the
+/// inventory untouched and therefore produces an empty diff.
+#[derive(Default)]
+pub struct Bank {
+    ledgers: HashMap<String, Ledger>,
+    active: Option<TradeSession>,
+    /// Items a user has asked to withdraw, laid out on the next trade they open.
+    pending: HashMap<String, Ledger>,
+}
+
+impl Bank {
+    /// Loads the ledgers from `bank.ron`, starting empty if the file is absent
+    /// or unreadable.
+    fn load() -> Bank {
+        match fs::read_to_string("bank.ron") {
+            Ok(contents) => Bank {
+                ledgers: ron::from_str(&contents).unwrap_or_default(),
+                ..Bank::default()
+            },
+            Err(_) => Bank::default(),
+        }
+    }
+
+    /// Persists the ledgers to `bank.ron`.
+    fn save(&self) {
+        match ron::ser::to_string_pretty(&self.ledgers, ron::ser::PrettyConfig::default()) {
+            Ok(data) => {
+                if let Err(err) = fs::write("bank.ron", data) {
+                    println!("Failed to save bank ledger: {:?}", err);
+                }
+            }
+            Err(err) => println!("Failed to serialize bank ledger: {:?}", err),
+        }
+    }
+
+    /// Returns the items stored on behalf of the given alias, if any.
+    fn balance(&self, alias: &str) -> Option<&Ledger> {
+        self.ledgers.get(alias)
+    }
+
+    /// Records the start of a trade with `alias`, snapshotting the bot's current
+    /// inventory. Returns `true` only on the first tick of a trade so callers
+    /// can run one-shot setup.
+    fn begin_trade(&mut self, alias: &str, client: &Client) -> bool {
+        if self.active.is_some() {
+            return false;
+        }
+        self.active = Some(TradeSession {
+            alias: alias.to_string(),
+            before: bot_inventory_counts(client),
+        });
+        true
+    }
+
+    /// Records a request from `alias` to withdraw `count` of `item` on their
+    /// next trade. `item` may be the full persistence id or just its final path
+    /// segment. Returns the resolved id, or an explanatory error for the user.
+    fn request_withdrawal(&mut self, alias: &str, item: &str, count: u32) -> Result<String, String> {
+        let ledger = self
+            .ledgers
+            .get(alias)
+            .ok_or_else(|| String::from("You have no items stored."))?;
+
+        let id = ledger
+            .keys()
+            .find(|k| k.as_str() == item || k.rsplit('.').next() == Some(item))
+            .cloned()
+            .ok_or_else(|| format!("You have no '{}' stored.", item))?;
+
+        let available = ledger[&id];
+        if count > available {
+            return Err(format!("You only have {} x{} stored.", id, available));
+        }
+
+        let entry = self.pending.entry(alias.to_string()).or_default();
+        let slot = entry.entry(id.clone()).or_insert(0);
+        *slot = (*slot + count).min(available);
+        Ok(id)
+    }
+
+    /// Lays out the items `alias` has requested to withdraw into the open trade
+    /// window, matching the request against the bot's inventory slots. The
+    /// request is consumed so it is not re-offered on a later trade.
+    fn offer_stored_items(&mut self, client: &mut Client, alias: &str) {
+        let mut remaining = match self.pending.remove(alias) {
+            Some(request) => request,
+            None => return,
+        };
+
+        let mut actions = Vec::new();
+        {
+            let ecs = client.state().ecs();
+            let inventories = ecs.read_component::<comp::Inventory>();
+            if let Some(inventory) = inventories.get(client.entity()) {
+                for (slot, item) in inventory.slots_with_id() {
+                    if let Some(item) = item {
+                        let id = item.persistence_item_id();
+                        if let Some(count) = remaining.get_mut(&id) {
+                            if *count > 0 {
+                                let quantity = (*count).min(item.amount());
+                                actions.push(TradeAction::AddItem {
+                                    item: slot,
+                                    quantity,
+                                    ours: true,
+                                });
+                                *count -= quantity;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        for action in actions {
+            client.perform_trade_action(action);
+        }
+    }
+
+    /// Reconciles the ledger once a trade ends: whatever the bot gained is
+    /// credited to the trading user as a deposit, whatever it lost is debited as
+    /// a withdrawal. Called when the trade is no longer pending.
+    fn commit_trade(&mut self, client: &Client) {
+        let session = match self.active.take() {
+            Some(session) => session,
+            None => return,
+        };
+
+        let after = bot_inventory_counts(client);
+        let ledger = self.ledgers.entry(session.alias.clone()).or_default();
+
+        let ids: HashSet<String> = session
+            .before
+            .keys()
+            .chain(after.keys())
+            .cloned()
+            .collect();
+
+        for id in ids {
+            let before = *session.before.get(&id).unwrap_or(&0);
+            let now = *after.get(&id).unwrap_or(&0);
+            if now > before {
+                *ledger.entry(id).or_insert(0) += now - before;
+            } else if before > now {
+                let entry = ledger.entry(id.clone()).or_insert(0);
+                *entry = entry.saturating_sub(before - now);
+                if *entry == 0 {
+                    ledger.remove(&id);
+                }
+            }
+        }
+
+        if ledger.is_empty() {
+            self.ledgers.remove(&session.alias);
+        }
+
+        self.save();
+    }
+}
+
+/// Tallies the bot's own inventory by item persistence id.
+fn bot_inventory_counts(client: &Client) -> Ledger {
+    let mut counts = Ledger::new();
+    let ecs = client.state().ecs();
+    let inventories = ecs.read_component::<comp::Inventory>();
+    if let Some(inventory) = inventories.get(client.entity()) {
+        for item in inventory.slots().flatten() {
+            *counts.entry(item.persistence_item_id()).or_insert(0) += item.amount();
+        }
+    }
+    counts
+}
+
+/// Resolves one party's offered trade slots into item definition ids and
+/// quantities, reading the party's live inventory from the ECS. This keeps the
+/// counterparty side on the same `persistence_item_id()` identity the price
+/// tables and [`COINS`] use, rather than the display names carried by a
+/// `ReducedInventory`.
+fn resolve_offer(client: &Client, pending_trade: &PendingTrade, party_idx: usize) -> Ledger {
+    let mut counts = Ledger::new();
+    let (offer, party) = match (
+        pending_trade.offers.get(party_idx),
+        pending_trade.parties.get(party_idx),
+    ) {
+        (Some(offer), Some(party)) => (offer, party),
+        _ => return counts,
+    };
+
+    let ecs = client.state().ecs();
+    let inventories = ecs.read_component::<comp::Inventory>();
+    let uid_allocator = ecs.read_resource::<UidAllocator>();
+    if let Some(entity) = uid_allocator.retrieve_entity_internal(party.0) {
+        if let Some(inventory) = inventories.get(entity) {
+            for (slot, quantity) in offer {
+                if let Some(item) = inventory.get(*slot) {
+                    *counts.entry(item.persistence_item_id()).or_insert(0) += quantity;
+                }
+            }
+        }
+    }
+    counts
+}
+
+/// Sums the coin value of a resolved offer using the given price table.
+fn offer_value(offer: &Ledger, prices: &HashMap<String, u32>) -> u32 {
+    offer
+        .iter()
+        .map(|(id, quantity)| prices.get(id).copied().unwrap_or(0) * quantity)
+        .sum()
+}
+
+/// Sums the number of coins in a resolved offer.
+fn coins_offered(offer: &Ledger) -> u32 {
+    offer.get(COINS).copied().unwrap_or(0)
+}
+
+/// Adds up to `amount` coins from the bot's inventory to its side of the trade.
+fn offer_coins(client: &mut Client, amount: u32) {
+    if amount == 0 {
+        return;
+    }
+
+    let mut remaining = amount;
+    let mut actions = Vec::new();
+    {
+        let ecs = client.state().ecs();
+        let inventories = ecs.read_component::<comp::Inventory>();
+        if let Some(inventory) = inventories.get(client.entity()) {
+            for (slot, item) in inventory.slots_with_id() {
+                if remaining == 0 {
+                    break;
+                }
+                if let Some(item) = item {
+                    if item.persistence_item_id() == COINS {
+                        let quantity = remaining.min(item.amount());
+                        actions.push(TradeAction::AddItem {
+                            item: slot,
+                            quantity,
+                            ours: true,
+                        });
+                        remaining -= quantity;
+                    }
+                }
+            }
+        }
+    }
+
+    for action in actions {
+        client.perform_trade_action(action);
+    }
+}
+
+/// Builds the controller inputs that keep the bot anchored at its configured
+/// position and facing. If no anchor is configured, or the bot is already close
+/// enough, this walks nowhere and only holds the facing.
+fn hold_position_inputs(client: &Client, config: &Config) -> comp::ControllerInputs {
+    let mut inputs = comp::ControllerInputs::default();
+
+    if let Some(position) = config.position {
+        if let Some(pos) = client.current::<comp::Pos>() {
+            let offset = Vec3::from(position) - pos.0;
+            let horizontal = Vec2::new(offset.x, offset.y);
+            if horizontal.magnitude() > POSITION_THRESHOLD {
+                inputs.move_dir = horizontal.normalized();
+            }
+        }
+    }
+
+    if let Some(orientation) = config.orientation {
+        inputs.look_dir = orientation.look_dir();
+    }
+
+    inputs
+}
+
 pub trait AliasOfUid {
     fn alias_of_uid(&self, uid: Uid) -> String;
 }
@@ -79,125 +512,322 @@ impl Until for Client {
     }
 }
 
-fn until_create_character(
-    client: &mut Client,
-    clock: &mut Clock,
-) -> Result<Vec<Event>, veloren_client::Error> {
-    let body = comp::body::humanoid::Body {
-        species: comp::body::humanoid::Species::Draugr,
-        body_type: comp::body::humanoid::BodyType::Female,
-        hair_style: 0,
-        beard: 1,
-        eyes: 0,
-        accessory: 1,
-        hair_color: 0,
-        skin: 0,
-        eye_color: 0,
-    };
+/// Sends a private message back to a player.
+fn tell(client: &mut Client, alias: &str, message: &str) {
+    client.send_command(
+        String::from("tell"),
+        vec![alias.to_string(), message.to_string()],
+    );
+}
 
-    client.create_character("Inventory Character".to_string(), None, None, body.into());
+/// The running bot: owns its connection, clock, parsed config, and bank state,
+/// and drives the world one frame at a time through [`Bot::tick`].
+pub struct Bot {
+    client: Client,
+    clock: Clock,
+    config: Config,
+    bank: Bank,
+    last_announcement: Instant,
+    /// Coins already laid out for the trade in progress. Tracked so buy-mode
+    /// payment tops up the shortfall when the counterparty raises their offer,
+    /// without re-stacking the same coins each tick before the async
+    /// `perform_trade_action` registers.
+    coins_committed: u32,
+}
 
-    println!("Created a new character.");
+impl Bot {
+    /// Builds a bot around an already-connected, logged-in client.
+    pub fn new(client: Client, config: Config) -> Bot {
+        Bot {
+            client,
+            clock: Clock::new(Duration::from_secs_f64(1.0 / 16.0)),
+            bank: Bank::load(),
+            config,
+            last_announcement: Instant::now(),
+            coins_committed: 0,
+        }
+    }
 
-    client.until(clock, |c| !c.character_list().loading)
-}
+    /// Requests the configured character, creating one if none exist, and blocks
+    /// until the bot is present in the world.
+    pub fn select_character(&mut self) {
+        // this is asynchronous. let's just keep loading.
+        self.client.load_character_list();
+
+        while self.client.presence().is_none() {
+            if self
+                .client
+                .tick(comp::ControllerInputs::default(), self.clock.dt(), |_| ())
+                .is_ok()
+            {
+                let character_list = self.client.character_list();
+                if !character_list.loading {
+                    if self.client.character_list().characters.len() > 0 {
+                        let character_ent = self.client.character_list().characters.first();
+                        let character = character_ent.unwrap().character.clone();
+                        self.client.request_character(character.id.unwrap());
+                        println!("Requesting character {}", character.alias);
+                    } else {
+                        // if we don't have a character, create one
+                        self.until_create_character()
+                            .expect("Unable to create a new character");
+                    }
+                }
+            }
 
-fn spawn_first_character(client: &mut Client, clock: &mut Clock) {
-    // this is asynchronous. let's just keep loading.
-    client.load_character_list();
+            self.client.cleanup();
+            self.clock.tick();
+        }
+    }
 
-    while client.presence().is_none() {
-        if client
-            .tick(comp::ControllerInputs::default(), clock.dt(), |_| ())
-            .is_ok()
-        {
-            let character_list = client.character_list();
-            if !character_list.loading {
-                if client.character_list().characters.len() > 0 {
-                    let character_ent = client.character_list().characters.first();
-                    let character = character_ent.unwrap().character.clone();
-                    client.request_character(character.id.unwrap());
-                    println!("Requesting character {}", character.alias);
-                } else {
-                    // if we don't have a character, create one
-                    until_create_character(client, clock)
-                        .expect("Unable to create a new character");
+    /// Creates the configured humanoid character and blocks until the character
+    /// list settles.
+    fn until_create_character(&mut self) -> Result<Vec<Event>, veloren_client::Error> {
+        let body = comp::body::humanoid::Body::from(&self.config.body);
+
+        self.client
+            .create_character(self.config.character_name.clone(), None, None, body.into());
+
+        println!("Created a new character.");
+
+        self.client
+            .until(&mut self.clock, |c| !c.character_list().loading)
+    }
+
+    /// Advances the world by one frame: ticks the client, reacts to world state,
+    /// and dispatches chat events. Errors on disconnect.
+    pub fn tick(&mut self) -> Result<(), veloren_client::Error> {
+        let inputs = hold_position_inputs(&self.client, &self.config);
+        let events = self.client.tick(inputs, self.clock.dt(), |_| {})?;
+
+        self.on_event();
+        self.maybe_announce();
+
+        for event in events {
+            match event {
+                Event::Chat(message) => {
+                    println!("{}", self.client.format_message(&message, true));
+
+                    if let ChatType::Tell(from, _to) = message.chat_type {
+                        self.handle_tell(from, &message.message);
+                    }
+                }
+                Event::Disconnect => {
+                    println!("Disconnected.");
                 }
+                _ => {}
             }
         }
 
-        client.cleanup();
-        clock.tick();
+        self.client.cleanup();
+        self.clock.tick();
+        Ok(())
     }
-}
 
-fn on_event(client: &mut Client, clock: &mut Clock) {
-    // if client is not present (i.e. not spawned in yet), they should spawn
-    // themselves NOW! â›ˆ
-    if client.presence().is_none() {
-        spawn_first_character(client, clock);
+    /// Broadcasts the configured announcement if the rate-limit interval has
+    /// elapsed since the last one.
+    fn maybe_announce(&mut self) {
+        if self.config.announcement.is_none() {
+            return;
+        }
+        let interval = Duration::from_secs(self.config.announcement_interval);
+        if self.last_announcement.elapsed() >= interval {
+            self.announce();
+        }
     }
 
-    // check if we have an invite
-    if let Some(last_invite) = client.invite() {
-        let inviter_id = last_invite.0;
-        if let Some(player_info) = client.player_list().get(&inviter_id) {
-            if player_info.player_alias == env::var("TARGET_USERNAME").unwrap() {
-                // we should the invite
-                client.accept_invite();
-            } else {
-                // otherwise decline
-                client.decline_invite();
+    /// Broadcasts the configured announcement immediately, resetting the timer.
+    fn announce(&mut self) {
+        if let Some(message) = self.config.announcement.clone() {
+            self.client
+                .send_command(String::from("say"), vec![message]);
+            self.last_announcement = Instant::now();
+        }
+    }
+
+    /// Reacts to the current world state: spawns if needed, answers invites, and
+    /// drives the active trade according to the configured mode.
+    fn on_event(&mut self) {
+        // if client is not present (i.e. not spawned in yet), they should spawn
+        // themselves NOW! â›ˆ
+        if self.client.presence().is_none() {
+            self.select_character();
+        }
+
+        // check if we have an invite
+        if let Some(last_invite) = self.client.invite() {
+            let inviter_id = last_invite.0;
+            if let Some(player_info) = self.client.player_list().get(&inviter_id) {
+                if self.config.is_authorized(&player_info.player_alias) {
+                    // we should the invite
+                    self.client.accept_invite();
+                } else {
+                    // otherwise decline
+                    self.client.decline_invite();
+                }
+            }
+        }
+
+        // drive the trade while one is in progress
+        if self.client.is_trading() {
+            if let Some((_trade_id, pending_trade, _)) = self.client.pending_trade().clone() {
+                if let Some(initiator_id) = pending_trade.parties.first() {
+                    let initiator = self.client.alias_of_uid(*initiator_id);
+                    if self.config.is_authorized(&initiator) {
+                        self.drive_trade(&initiator, &pending_trade);
+                    }
+                }
+            }
+        } else {
+            // no trade in progress; clear the per-trade payment guard.
+            self.coins_committed = 0;
+            if self.bank.active.is_some() {
+                // the trade just ended (completed or cancelled); reconcile the
+                // ledger against whatever actually changed hands.
+                self.bank.commit_trade(&self.client);
             }
         }
     }
 
-    // if we are currently in a trade, always accept
-    if client.is_trading() {
-        if let Some((_trade_id, pending_trade, _)) = client.pending_trade().clone() {
-            if let Some(initiator_id) = pending_trade.parties.first() {
-                if client.alias_of_uid(*initiator_id) == env::var("TARGET_USERNAME").unwrap() {
-                    // keep accepting the trade if our intended user is the initiator
-                    client.perform_trade_action(TradeAction::Accept(pending_trade.phase));
-
-                    // get inventories and balance
-                    let ecs = &client.state().ecs();
-                    let inventories = ecs.read_component::<comp::Inventory>();
-                    let get_inventory = |uid: Uid| {
-                        if let Some(entity) = ecs
-                            .read_resource::<UidAllocator>()
-                            .retrieve_entity_internal(uid.0)
-                        {
-                            inventories.get(entity)
-                        } else {
-                            None
-                        }
-                    };
+    /// Applies the configured trade mode to the open trade with `initiator`.
+    fn drive_trade(&mut self, initiator: &str, pending_trade: &PendingTrade) {
+        let bot_idx = pending_trade
+            .parties
+            .iter()
+            .position(|party| Some(*party) == self.client.uid())
+            .unwrap_or(1);
+        let initiator_idx = 1 - bot_idx;
+
+        // resolve both sides to definition ids so we can value the current offers
+        let bot_offer = resolve_offer(&self.client, pending_trade, bot_idx);
+        let initiator_offer = resolve_offer(&self.client, pending_trade, initiator_idx);
+
+        match self.config.mode {
+            TradeMode::Take => {
+                // store whatever is handed over, and lay out any items the user
+                // asked to withdraw.
+                self.client
+                    .perform_trade_action(TradeAction::Accept(pending_trade.phase));
+                if self.bank.begin_trade(initiator, &self.client) {
+                    self.bank.offer_stored_items(&mut self.client, initiator);
+                }
+            }
+            TradeMode::Buy => {
+                // pay coins to match the value of the incoming goods, only
+                // accepting once our side covers it.
+                let incoming = offer_value(&initiator_offer, &self.config.buy_prices);
+                let paid = coins_offered(&bot_offer);
+                // track coins against the total we've committed rather than the
+                // live offer: perform_trade_action is async, so the coins added
+                // this tick won't show up in `paid` until later. If the
+                // counterparty raises their offer, `incoming` grows past what we
+                // committed and we top up the difference.
+                if self.coins_committed < incoming {
+                    offer_coins(&mut self.client, incoming - self.coins_committed);
+                    self.coins_committed = incoming;
+                }
+                if incoming > 0 && paid >= incoming {
+                    self.client
+                        .perform_trade_action(TradeAction::Accept(pending_trade.phase));
+                }
+            }
+            TradeMode::Sell => {
+                // lay out the items the user asked to withdraw, then only accept
+                // once their coins cover our sell-price valuation of those goods.
+                if self.bank.begin_trade(initiator, &self.client) {
+                    self.bank.offer_stored_items(&mut self.client, initiator);
+                }
+                let goods = offer_value(&bot_offer, &self.config.sell_prices);
+                let paid = coins_offered(&initiator_offer);
+                if goods > 0 && paid >= goods {
+                    self.client
+                        .perform_trade_action(TradeAction::Accept(pending_trade.phase));
+                }
+            }
+        }
+    }
 
-                    let mut party_inventories = [None, None];
+    /// Parses a tell from an authorized user as a bank command and responds.
+    /// Tells from unauthorized players are ignored.
+    fn handle_tell(&mut self, from: Uid, body: &str) {
+        let sender = self.client.alias_of_uid(from);
+        if !self.config.is_authorized(&sender) {
+            return;
+        }
 
-                    for (i, party) in pending_trade.parties.iter().enumerate() {
-                        println!("Fetching inventory {}", i);
-                        match get_inventory(*party) {
-                            Some(inventory) => {
-                                party_inventories[i] = Some(ReducedInventory::from(inventory))
+        let mut parts = body.split_whitespace();
+        let command = parts.next().unwrap_or("").to_lowercase();
+        match command.as_str() {
+            "balance" | "list" => {
+                let reply = match self.bank.balance(&sender) {
+                    Some(ledger) if !ledger.is_empty() => {
+                        let mut items: Vec<String> = ledger
+                            .iter()
+                            .map(|(id, count)| format!("{} x{}", id, count))
+                            .collect();
+                        items.sort();
+                        format!("Your stored items: {}", items.join(", "))
+                    }
+                    _ => String::from("You have no items stored."),
+                };
+                tell(&mut self.client, &sender, &reply);
+            }
+            "deposit" => {
+                self.client
+                    .send_invite(from, comp::invite::InviteKind::Trade);
+                tell(
+                    &mut self.client,
+                    &sender,
+                    "Trade invite sent. Add the items you want to deposit.",
+                );
+            }
+            "withdraw" => {
+                let item = parts.next();
+                let count = parts.next().and_then(|c| c.parse::<u32>().ok());
+                match (item, count) {
+                    (Some(item), Some(count)) => {
+                        match self.bank.request_withdrawal(&sender, item, count) {
+                            Ok(id) => {
+                                self.client
+                                    .send_invite(from, comp::invite::InviteKind::Trade);
+                                tell(
+                                    &mut self.client,
+                                    &sender,
+                                    &format!(
+                                        "Trade invite sent. Accept to withdraw {} x{}.",
+                                        id, count
+                                    ),
+                                );
                             }
-                            None => continue,
-                        };
+                            Err(err) => tell(&mut self.client, &sender, &err),
+                        }
                     }
+                    _ => tell(&mut self.client, &sender, "Usage: withdraw <item> <count>"),
+                }
+            }
+            "announce" => {
+                if self.config.is_admin(&sender) {
+                    self.announce();
+                    tell(&mut self.client, &sender, "Announcement broadcast.");
+                } else {
+                    tell(&mut self.client, &sender, "You are not allowed to do that.");
                 }
             }
+            _ => tell(
+                &mut self.client,
+                &sender,
+                "Commands: balance, list, withdraw <item> <count>, deposit",
+            ),
         }
     }
 }
 
 fn main() {
     println!("Starting veloren-inventory-bot...");
-    let mut clock = Clock::new(Duration::from_secs_f64(1.0 / 16.0));
-    let username = String::from(env::var("BOT_USERNAME").expect("$BOT_USERNAME is not set"));
-    let password = String::from(env::var("BOT_PASSWORD").expect("$BOT_PASSWORD is not set"));
-    env::var("TARGET_USERNAME").expect("$TARGET_USERNAME is not set");
-    let server_addr = String::from("server.veloren.net:14004");
+    let config = Config::load();
+    let username = config.username.clone();
+    let password = config.password.clone();
+    let server_addr = config.server_addr.clone();
 
     let runtime = Arc::new(Runtime::new().unwrap());
     let runtime2 = Arc::clone(&runtime);
@@ -230,38 +860,12 @@ fn main() {
 
     println!("Logged in as {}", username);
 
-    loop {
-        let events = match client.tick(comp::ControllerInputs::default(), clock.dt(), |_| {}) {
-            Ok(events) => {
-                on_event(&mut client, &mut clock);
-                events
-            }
-            Err(err) => {
-                println!("Error: {:?}", err);
-                break;
-            }
-        };
-
-        for event in events {
-            match event {
-                Event::Chat(message) => {
-                    println!("{}", client.format_message(&message, true));
+    let mut bot = Bot::new(client, config);
 
-                    if let ChatType::Tell(from, _to) = message.chat_type {
-                        let sender = client.alias_of_uid(from);
-                        if sender == env::var("TARGET_USERNAME").unwrap() {
-                            client.send_invite(from, comp::invite::InviteKind::Trade);
-                        }
-                    }
-                }
-                Event::Disconnect => {
-                    println!("Disconnected.");
-                }
-                _ => {}
-            }
+    loop {
+        if let Err(err) = bot.tick() {
+            println!("Error: {:?}", err);
+            break;
         }
-
-        client.cleanup();
-        clock.tick();
     }
 }